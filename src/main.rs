@@ -1,17 +1,59 @@
 use clap::{Arg, ArgAction, Command};
 use flexi_logger::{detailed_format, Duplicate, FileSpec, Logger};
-use log::error;
+use log::{error, warn};
 use owo_colors::colored::*;
 use rayon::prelude::*;
-use regex::{Match, Regex};
+use regex::{Match, Regex, RegexSet};
+use serde::{Deserialize, Serialize};
 
 use std::{
     fs,
-    io::{self, BufRead},
+    io::{self, BufRead, IsTerminal},
     path::{Path, PathBuf},
     process,
 };
 
+// highlight colors cycled per pattern index (RGB truecolor)
+const PALETTE: [(u8, u8, u8); 6] = [
+    (112, 110, 255), // the original highlight color stays first
+    (255, 135, 0),
+    (0, 215, 95),
+    (255, 95, 135),
+    (95, 215, 255),
+    (215, 215, 0),
+];
+
+// persisted defaults, written into the `sp` config directory on first run
+#[derive(Serialize, Deserialize)]
+struct Config {
+    // highlight color for the first pattern, as an RGB triple
+    match_color: [u8; 3],
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            match_color: [PALETTE[0].0, PALETTE[0].1, PALETTE[0].2],
+        }
+    }
+}
+
+// decides whether and in which color a match gets painted
+struct Colorizer {
+    enabled: bool,
+    palette: [(u8, u8, u8); PALETTE.len()],
+}
+
+impl Colorizer {
+    fn paint(&self, text: &str, idx: usize) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        let (r, g, b) = self.palette[idx % self.palette.len()];
+        text.truecolor(r, g, b).to_string()
+    }
+}
+
 fn main() {
     // handle Ctrl+C
     ctrlc::set_handler(move || {
@@ -32,6 +74,48 @@ fn main() {
     let matches = sp().get_matches();
     let parallel_flag = matches.get_flag("parallel");
     let matches_flag = matches.get_flag("matches");
+    let replace = matches.get_one::<String>("replace").map(|s| s.to_string());
+    let fixed_flag = matches.get_flag("fixed-strings");
+    let ignore_case_flag = matches.get_flag("ignore-case");
+    let word_flag = matches.get_flag("word-regexp");
+    let line_flag = matches.get_flag("line-regexp");
+    let after_opt = matches.get_one::<usize>("after").copied();
+    let before_opt = matches.get_one::<usize>("before").copied();
+    let context_opt = matches.get_one::<usize>("context").copied();
+    // -C sets both windows; otherwise fall back to -A/-B individually
+    let after = context_opt.or(after_opt).unwrap_or(0);
+    let before = context_opt.or(before_opt).unwrap_or(0);
+    let context_flag = after_opt.is_some() || before_opt.is_some() || context_opt.is_some();
+    let only_matching_flag = matches.get_flag("only-matching");
+    let count_flag = matches.get_flag("count");
+
+    // load the persisted palette, then let CLI flags override it
+    let config = load_or_create_config(&config_dir);
+    let mut palette = PALETTE;
+    palette[0] = (
+        config.match_color[0],
+        config.match_color[1],
+        config.match_color[2],
+    );
+    if let Some(color) = matches.get_one::<String>("match-color") {
+        match parse_color(color) {
+            Some(rgb) => palette[0] = rgb,
+            None => {
+                error!("Invalid color '{color}': expected 'R,G,B' with values 0-255");
+                process::exit(1);
+            }
+        }
+    }
+    let color_enabled = match matches.get_one::<String>("color").map(|s| s.as_str()) {
+        Some("always") => true,
+        Some("never") => false,
+        // auto: colorize only when writing to a terminal
+        _ => io::stdout().is_terminal(),
+    };
+    let colorizer = Colorizer {
+        enabled: color_enabled,
+        palette,
+    };
 
     if let Some(_) = matches.subcommand_matches("log") {
         show_logs(&config_dir);
@@ -40,31 +124,173 @@ fn main() {
     } else if let Some(_) = matches.subcommand_matches("syntax") {
         show_regex_syntax();
     } else {
+        // collect every pattern: the positional one plus any repeated `-e/--regexp`
+        let mut patterns = Vec::new();
         if let Some(pattern) = matches.get_one::<String>("pattern") {
-            let re = Regex::new(pattern).unwrap();
+            patterns.push(pattern.to_string());
+        }
+        if let Some(regexps) = matches.get_many::<String>("regexp") {
+            patterns.extend(regexps.map(|p| p.to_string()));
+        }
 
-            let pipe = read_pipe();
+        if patterns.is_empty() {
+            let _ = sp().print_help();
+            process::exit(0);
+        }
 
-            if parallel_flag {
-                let lines = par_split_pipe_by_lines(pipe);
-                lines.into_par_iter().for_each(|line| {
-                    let captures = search_regex(&line, re.clone());
-                    if let Some(high_line) = highlight_capture(&line, &captures, matches_flag) {
-                        println!("{}", high_line);
-                    }
+        // apply the matcher-configuration flags to every raw pattern
+        let patterns: Vec<String> = patterns
+            .iter()
+            .map(|p| build_pattern(p, fixed_flag, ignore_case_flag, word_flag, line_flag))
+            .collect();
+
+        let regexes: Vec<Regex> = patterns
+            .iter()
+            .map(|p| {
+                Regex::new(p).unwrap_or_else(|err| {
+                    error!("Invalid pattern '{p}': {err}");
+                    process::exit(1);
                 })
+            })
+            .collect();
+        // single automaton to cheaply tell which patterns hit a line
+        let set = RegexSet::new(&patterns).unwrap_or_else(|err| {
+            error!("Invalid pattern: {err}");
+            process::exit(1);
+        });
+
+        let pipe = read_pipe();
+
+        let process_line = |line: &str| -> Option<String> {
+            if let Some(template) = &replace {
+                replace_regex(line, &regexes, &set, template, matches_flag, &colorizer)
             } else {
-                let lines = split_pipe_by_lines(pipe);
-                lines.into_iter().for_each(|line| {
-                    let captures = search_regex(&line, re.clone());
-                    if let Some(high_line) = highlight_capture(&line, &captures, matches_flag) {
-                        println!("{}", high_line);
-                    }
-                })
+                let captures = search_regex(line, &regexes, &set);
+                highlight_capture(line, &captures, matches_flag, &colorizer)
+            }
+        };
+
+        if count_flag {
+            // tally matching lines (or individual matches with `-o`) and print one number
+            let total: usize = if parallel_flag {
+                par_split_pipe_by_lines(pipe)
+                    .par_iter()
+                    .map(|line| count_line(line, &regexes, &set, only_matching_flag))
+                    .sum()
+            } else {
+                split_pipe_by_lines(pipe)
+                    .iter()
+                    .map(|line| count_line(line, &regexes, &set, only_matching_flag))
+                    .sum()
+            };
+            println!("{}", total);
+        } else if only_matching_flag {
+            // print just the matched substrings, one per line, still colorized
+            let print_matches = |line: &str| {
+                for (m, idx) in search_regex(line, &regexes, &set) {
+                    println!("{}", colorizer.paint(m.as_str(), idx));
+                }
+            };
+            if parallel_flag {
+                par_split_pipe_by_lines(pipe)
+                    .par_iter()
+                    .for_each(|line| print_matches(line));
+            } else {
+                split_pipe_by_lines(pipe)
+                    .iter()
+                    .for_each(|line| print_matches(line));
+            }
+        } else if context_flag {
+            // context output is order-dependent, so it can't run in parallel
+            if parallel_flag {
+                warn!("Context lines require ordered output; ignoring '-p/--parallel'");
             }
+            let lines = split_pipe_by_lines(pipe);
+            print_with_context(&lines, &regexes, &set, before, after, process_line);
+        } else if parallel_flag {
+            let lines = par_split_pipe_by_lines(pipe);
+            lines.into_par_iter().for_each(|line| {
+                if let Some(out) = process_line(&line) {
+                    println!("{}", out);
+                }
+            })
         } else {
-            let _ = sp().print_help();
-            process::exit(0);
+            let lines = split_pipe_by_lines(pipe);
+            lines.into_iter().for_each(|line| {
+                if let Some(out) = process_line(&line) {
+                    println!("{}", out);
+                }
+            })
+        }
+    }
+}
+
+fn count_line(line: &str, regexes: &[Regex], set: &RegexSet, only_matching: bool) -> usize {
+    let captures = search_regex(line, regexes, set);
+    if only_matching {
+        // count every match on the line
+        captures.len()
+    } else if captures.is_empty() {
+        0
+    } else {
+        // count the line once, regardless of how many matches it holds
+        1
+    }
+}
+
+fn print_with_context<F>(
+    lines: &[String],
+    regexes: &[Regex],
+    set: &RegexSet,
+    before: usize,
+    after: usize,
+    process_line: F,
+) where
+    F: Fn(&str) -> Option<String>,
+{
+    if lines.is_empty() {
+        return;
+    }
+
+    // which lines hold at least one match
+    let matched: Vec<bool> = lines
+        .iter()
+        .map(|line| !search_regex(line, regexes, set).is_empty())
+        .collect();
+
+    // expand each match by its context window and merge overlapping/adjacent ranges
+    let last = lines.len() - 1;
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, &is_match) in matched.iter().enumerate() {
+        if !is_match {
+            continue;
+        }
+        let start = i.saturating_sub(before);
+        let end = (i + after).min(last);
+        if let Some(prev) = ranges.last_mut() {
+            if start <= prev.1 + 1 {
+                prev.1 = prev.1.max(end);
+                continue;
+            }
+        }
+        ranges.push((start, end));
+    }
+
+    for (group, &(start, end)) in ranges.iter().enumerate() {
+        // separate non-adjacent groups the way ripgrep does
+        if group > 0 {
+            println!("--");
+        }
+        for idx in start..=end {
+            if matched[idx] {
+                // matching lines keep their highlight/replacement
+                if let Some(out) = process_line(&lines[idx]) {
+                    println!("{}", out);
+                }
+            } else {
+                // context lines print uncolored
+                println!("{}", lines[idx]);
+            }
         }
     }
 }
@@ -94,13 +320,128 @@ fn par_split_pipe_by_lines(pipe: String) -> Vec<String> {
         .collect::<Vec<_>>()
 }
 
-fn search_regex(hay: &str, reg: Regex) -> Vec<Match> {
-    let captures: Vec<_> = reg.find_iter(hay).collect();
+fn search_regex<'a>(
+    hay: &'a str,
+    regexes: &[Regex],
+    set: &RegexSet,
+) -> Vec<(Match<'a>, usize)> {
+    // cheaply skip lines that no pattern touches
+    if !set.is_match(hay) {
+        return Vec::new();
+    }
+
+    // only run the full scan for the patterns that actually matched
+    let mut captures = Vec::new();
+    for idx in set.matches(hay).into_iter() {
+        for m in regexes[idx].find_iter(hay) {
+            captures.push((m, idx));
+        }
+    }
 
     captures
 }
 
-fn highlight_capture(line: &str, captures: &Vec<Match>, matches_flag: bool) -> Option<String> {
+fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    // accept a plain `R,G,B` triple with values in 0-255
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let r = parts[0].trim().parse().ok()?;
+    let g = parts[1].trim().parse().ok()?;
+    let b = parts[2].trim().parse().ok()?;
+    Some((r, g, b))
+}
+
+fn build_pattern(
+    raw: &str,
+    fixed: bool,
+    ignore_case: bool,
+    word: bool,
+    line: bool,
+) -> String {
+    // escape first so the remaining wrappers apply to a valid sub-expression
+    let mut pattern = if fixed {
+        regex::escape(raw)
+    } else {
+        raw.to_string()
+    };
+
+    if word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    if line {
+        pattern = format!("^(?:{})$", pattern);
+    }
+    if ignore_case {
+        pattern = format!("(?i){}", pattern);
+    }
+
+    pattern
+}
+
+fn replace_regex(
+    line: &str,
+    regexes: &[Regex],
+    set: &RegexSet,
+    template: &str,
+    matches_flag: bool,
+    colorizer: &Colorizer,
+) -> Option<String> {
+    if !set.is_match(line) {
+        return if matches_flag {
+            None
+        } else {
+            Some(line.to_string())
+        };
+    }
+
+    // expand the template against every capture from every matched pattern;
+    // `Captures::expand` implements the `$1`/`${name}`/`$$` interpolation grammar
+    let mut edits: Vec<(usize, usize, String, usize)> = Vec::new();
+    for idx in set.matches(line).into_iter() {
+        for cap in regexes[idx].captures_iter(line) {
+            let whole = cap.get(0).unwrap();
+            let mut dst = String::new();
+            cap.expand(template, &mut dst);
+            edits.push((whole.start(), whole.end(), dst, idx));
+        }
+    }
+
+    if edits.is_empty() {
+        return if matches_flag {
+            None
+        } else {
+            Some(line.to_string())
+        };
+    }
+
+    // walk matches left to right, keeping the earlier/longer one on overlap
+    edits.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+    let mut new = String::with_capacity(line.len());
+    let mut last_match = 0;
+    for (start, end, expanded, idx) in edits {
+        if start < last_match {
+            continue;
+        }
+        new.push_str(&line[last_match..start]);
+
+        new.push_str(&colorizer.paint(&expanded, idx));
+
+        last_match = end;
+    }
+    new.push_str(&line[last_match..]);
+
+    Some(new)
+}
+
+fn highlight_capture(
+    line: &str,
+    captures: &[(Match, usize)],
+    matches_flag: bool,
+    colorizer: &Colorizer,
+) -> Option<String> {
     if captures.is_empty() {
         if matches_flag {
             return None;
@@ -109,15 +450,23 @@ fn highlight_capture(line: &str, captures: &Vec<Match>, matches_flag: bool) -> O
         }
     }
 
+    // order matches left to right so `last_match` never moves backwards
+    let mut ordered = captures.to_vec();
+    ordered.sort_by(|(a, _), (b, _)| a.start().cmp(&b.start()).then(b.end().cmp(&a.end())));
+
     // pre-allocate enough memory for original line + estimated additional space for ANSI codes (est. each color adds ~20 bytes)
     // this reduces the number of times the string's buffer needs to be reallocated as elements are added
-    let mut new = String::with_capacity(line.len() + captures.len() * 20);
+    let mut new = String::with_capacity(line.len() + ordered.len() * 20);
 
     let mut last_match = 0;
-    for cap in captures {
+    for (cap, idx) in ordered {
+        // on overlap keep the earlier/longer match and drop the rest
+        if cap.start() < last_match {
+            continue;
+        }
         new.push_str(&line[last_match..cap.start()]);
 
-        let pattern = cap.as_str().truecolor(112, 110, 255).to_string();
+        let pattern = colorizer.paint(cap.as_str(), idx);
         new.push_str(&pattern);
 
         last_match = cap.end();
@@ -163,6 +512,122 @@ fn sp() -> Command {
                 .action(ArgAction::Set)
                 .value_name("PATTERN"),
         )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .help("Control when to use colors")
+                .long_help(format!(
+                    "{}\n{}",
+                    "Control when to use colors",
+                    "'auto' only colorizes when stdout is a terminal",
+                ))
+                .action(ArgAction::Set)
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("match-color")
+                .long("match-color")
+                .help("Override the first pattern's highlight color as an 'R,G,B' triple")
+                .action(ArgAction::Set)
+                .value_name("R,G,B"),
+        )
+        .arg(
+            Arg::new("only-matching")
+                .short('o')
+                .long("only-matching")
+                .help("Print only the matched parts of a line, one per line")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("count")
+                .short('c')
+                .long("count")
+                .help("Print a count of matching lines (or matches with '-o')")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("after")
+                .short('A')
+                .long("after-context")
+                .help("Print N lines of trailing context after each match")
+                .action(ArgAction::Set)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("before")
+                .short('B')
+                .long("before-context")
+                .help("Print N lines of leading context before each match")
+                .action(ArgAction::Set)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("context")
+                .short('C')
+                .long("context")
+                .help("Print N lines of context around each match")
+                .action(ArgAction::Set)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("fixed-strings")
+                .short('F')
+                .long("fixed-strings")
+                .help("Treat the pattern as a literal string")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore-case")
+                .short('i')
+                .long("ignore-case")
+                .help("Match case-insensitively")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("word-regexp")
+                .short('w')
+                .long("word-regexp")
+                .help("Only match the pattern surrounded by word boundaries")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("line-regexp")
+                .short('x')
+                .long("line-regexp")
+                .help("Only match the pattern against the whole line")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("replace")
+                .short('r')
+                .long("replace")
+                .help("Replace each match using a template with capture references")
+                .long_help(format!(
+                    "{}\n{}",
+                    "Replace each match using a template with capture references",
+                    "Use $1/${name} for groups and $$ for a literal dollar sign",
+                ))
+                .action(ArgAction::Set)
+                .value_name("TEMPLATE"),
+        )
+        .arg(
+            Arg::new("regexp")
+                .short('e')
+                .long("regexp")
+                .help("Add a search pattern (can be used multiple times)")
+                .long_help(format!(
+                    "{}\n{}",
+                    "Add a search pattern (can be used multiple times)",
+                    "Each pattern's hits get their own color; the six colors repeat after the sixth pattern",
+                ))
+                .action(ArgAction::Append)
+                .value_name("PATTERN"),
+        )
         .arg(
             Arg::new("parallel")
                 .short('p')
@@ -398,6 +863,31 @@ fn check_create_config_dir() -> io::Result<PathBuf> {
     Ok(new_dir)
 }
 
+fn load_or_create_config(config_dir: &PathBuf) -> Config {
+    let config_path = Path::new(config_dir).join("sp.toml");
+
+    if config_path.exists() {
+        match fs::read_to_string(&config_path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(config) => return config,
+                Err(err) => error!("Unable to parse config file, using defaults: {err}"),
+            },
+            Err(err) => error!("Unable to read config file, using defaults: {err}"),
+        }
+        return Config::default();
+    }
+
+    // write a default config on first run so users have something to edit
+    let config = Config::default();
+    if let Ok(content) = toml::to_string(&config) {
+        if let Err(err) = fs::write(&config_path, content) {
+            error!("Unable to write default config file: {err}");
+        }
+    }
+
+    config
+}
+
 fn init_logger(config_dir: &PathBuf) {
     let _logger = Logger::try_with_str("info") // log info, warn and error
         .unwrap()